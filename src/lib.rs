@@ -0,0 +1,878 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::server::TlsStream;
+use url::Url;
+
+pub enum Status {
+    Input,
+    SensitiveInput,
+    Success,
+    RedirectTemporary,
+    RedirectPermanent,
+    TemporaryFailure,
+    PermanentFailure,
+    ProxyRequestRefused,
+    NotFound,
+    BadRequest,
+    CertificateRequired,
+    CertificateNotAuthorized,
+}
+
+impl Status {
+    pub fn code(&self) -> u8 {
+        match self {
+            Status::Input => 10,
+            Status::SensitiveInput => 11,
+            Status::Success => 20,
+            Status::RedirectTemporary => 30,
+            Status::RedirectPermanent => 31,
+            Status::TemporaryFailure => 40,
+            Status::PermanentFailure => 50,
+            Status::ProxyRequestRefused => 53,
+            Status::NotFound => 51,
+            Status::BadRequest => 59,
+            Status::CertificateRequired => 60,
+            Status::CertificateNotAuthorized => 61,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Status> {
+        match code {
+            10 => Some(Status::Input),
+            11 => Some(Status::SensitiveInput),
+            20 => Some(Status::Success),
+            30 => Some(Status::RedirectTemporary),
+            31 => Some(Status::RedirectPermanent),
+            40 => Some(Status::TemporaryFailure),
+            50 => Some(Status::PermanentFailure),
+            53 => Some(Status::ProxyRequestRefused),
+            51 => Some(Status::NotFound),
+            59 => Some(Status::BadRequest),
+            60 => Some(Status::CertificateRequired),
+            61 => Some(Status::CertificateNotAuthorized),
+            _ => None,
+        }
+    }
+}
+
+pub struct ResponseHeader {
+    pub status: Status,
+    pub meta: String,
+}
+
+impl ResponseHeader {
+    pub fn new(status: Status, meta: &str) -> ResponseHeader {
+        ResponseHeader {
+            status,
+            meta: String::from(meta),
+        }
+    }
+
+    fn render(&self) -> String {
+        format!("{} {}\r\n", self.status.code(), &self.meta)
+    }
+}
+
+/// A response body is either fully generated in memory (small, dynamic
+/// responses) or an open file handle to stream straight to the client
+/// without buffering the whole thing.
+pub enum ResponseBody {
+    Bytes(Vec<u8>),
+    File(tokio::fs::File),
+}
+
+pub struct Response {
+    pub header: ResponseHeader,
+    pub body: Option<ResponseBody>,
+}
+impl Response {
+    fn render_header(&self) -> Vec<u8> {
+        self.header.render().into_bytes()
+    }
+}
+
+/// Maps a file extension to its Gemini/MIME content type, falling back to
+/// `application/octet-stream` for anything unrecognized.
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("gmi") | Some("gemini") => "text/gemini",
+        Some("txt") => "text/plain",
+        Some("html") | Some("htm") => "text/html",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Characters to percent-encode in directory-listing link targets, on top of
+/// the ASCII control characters.
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'%');
+
+/// Builds a gemtext directory listing: one `=> name` link per entry,
+/// directories sorted first (each suffixed with `/`), then files, both
+/// alphabetically.
+fn render_directory_listing(dir: &Path) -> Vec<u8> {
+    let mut entries: Vec<(String, bool)> = fs::read_dir(dir)
+        .map(|read_dir| {
+            read_dir
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let is_dir = entry.path().is_dir();
+                    (entry.file_name().to_string_lossy().into_owned(), is_dir)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut gemtext = String::new();
+    for (name, is_dir) in entries {
+        let suffix = if is_dir { "/" } else { "" };
+        let encoded_name = utf8_percent_encode(&name, PATH_SEGMENT_ENCODE_SET);
+        gemtext.push_str(&format!("=> {encoded_name}{suffix} {name}{suffix}\n"));
+    }
+    gemtext.into_bytes()
+}
+
+/// Renders a canonicalized, in-root path as a Gemini-style path (leading
+/// slash, `/`-separated) relative to `canonical_root`, so it can be matched
+/// against `protected_paths` regardless of how the original request path
+/// was spelled (doubled slashes, `..`, percent-encoding).
+fn path_relative_to_root(path: &Path, canonical_root: &Path) -> String {
+    let relative = path.strip_prefix(canonical_root).unwrap_or(path);
+    let mut normalized = String::from("/");
+    normalized.push_str(
+        &relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    );
+    normalized
+}
+
+fn parse_request_line(request_line: String) -> Result<Url, &'static str> {
+    if request_line.starts_with('\u{feff}') {
+        Err("The request MUST NOT begin with a U+FEFF byte order mark.")
+    } else if request_line.len() > 1024 {
+        Err("URL is too long. Maximum length is 1024 bytes.")
+    } else {
+        match Url::parse(&request_line) {
+            Ok(u) => Ok(u),
+            Err(_) => Err("Error parsing the url"),
+        }
+    }
+}
+
+/// A parsed, validated Gemini request handed to a [`Handler`].
+pub struct Request {
+    pub url: Url,
+    pub client_cert_fingerprint: Option<String>,
+}
+
+/// Dynamic request handling extension point: implement this to serve
+/// anything other than static files from the document root.
+#[async_trait::async_trait]
+pub trait Handler: Send + Sync {
+    async fn handle(&self, request: &Request) -> Response;
+}
+
+/// The default handler: serves static files out of `root`, with optional
+/// client-certificate gating and directory auto-indexing.
+pub struct FileHandler {
+    pub root: PathBuf,
+    pub protected_paths: Vec<String>,
+    pub allowed_fingerprints: Vec<String>,
+    pub auto_index: bool,
+}
+
+#[async_trait::async_trait]
+impl Handler for FileHandler {
+    async fn handle(&self, request: &Request) -> Response {
+        let mut read_path = PathBuf::from(&self.root);
+        let mut path = request.url.path();
+
+        if path.is_empty() {
+            path = "/";
+        }
+
+        let decoded_path = percent_decode_str(path)
+            .decode_utf8()
+            .unwrap_or_else(|_| path.into());
+
+        read_path.push(decoded_path.trim_start_matches('/'));
+
+        // Canonicalize (and verify containment under `root`) before any other
+        // filesystem call on `read_path` — `exists()`/`is_dir()` on the raw,
+        // attacker-controlled path would otherwise leak whether files outside
+        // the document root exist.
+        let canonical_root = match fs::canonicalize(&self.root) {
+            Ok(root) => root,
+            Err(_) => {
+                return Response {
+                    header: ResponseHeader::new(Status::TemporaryFailure, "Internal Server Error"),
+                    body: None,
+                };
+            }
+        };
+
+        read_path = match fs::canonicalize(&read_path) {
+            Ok(canonical) if canonical.starts_with(&canonical_root) => canonical,
+            Ok(_) => {
+                return Response {
+                    header: ResponseHeader::new(Status::BadRequest, "Invalid path."),
+                    body: None,
+                };
+            }
+            Err(_) => {
+                return Response {
+                    header: ResponseHeader::new(Status::NotFound, "Not Found"),
+                    body: None,
+                };
+            }
+        };
+
+        let normalized_path = path_relative_to_root(&read_path, &canonical_root);
+
+        if self
+            .protected_paths
+            .iter()
+            .any(|p| normalized_path == p.as_str() || normalized_path.starts_with(&format!("{p}/")))
+        {
+            match &request.client_cert_fingerprint {
+                None => {
+                    return Response {
+                        header: ResponseHeader::new(
+                            Status::CertificateRequired,
+                            "A client certificate is required for this resource.",
+                        ),
+                        body: None,
+                    };
+                }
+                Some(fingerprint) if !self.allowed_fingerprints.contains(fingerprint) => {
+                    return Response {
+                        header: ResponseHeader::new(
+                            Status::CertificateNotAuthorized,
+                            "This certificate is not authorized for this resource.",
+                        ),
+                        body: None,
+                    };
+                }
+                Some(_) => {}
+            }
+        }
+
+        if read_path.is_dir() && !decoded_path.ends_with('/') {
+            return Response {
+                header: ResponseHeader::new(Status::RedirectTemporary, &format!("{path}/")),
+                body: None,
+            };
+        }
+
+        if read_path.is_dir() {
+            let index_path = read_path.join("index.gmi");
+            if index_path.exists() {
+                read_path = index_path;
+            } else if self.auto_index {
+                return Response {
+                    header: ResponseHeader::new(Status::Success, "text/gemini"),
+                    body: Some(ResponseBody::Bytes(render_directory_listing(&read_path))),
+                };
+            } else {
+                return Response {
+                    header: ResponseHeader::new(Status::NotFound, "Not Found"),
+                    body: None,
+                };
+            }
+        }
+
+        if let Ok(file) = tokio::fs::File::open(&read_path).await {
+            Response {
+                header: ResponseHeader::new(Status::Success, content_type(&read_path)),
+                body: Some(ResponseBody::File(file)),
+            }
+        } else {
+            Response {
+                header: ResponseHeader::new(Status::TemporaryFailure, "Internal Server Error"),
+                body: None,
+            }
+        }
+    }
+}
+
+/// Server-wide settings that apply before a request reaches a [`Handler`].
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub host: String,
+    pub port: u16,
+    pub allowed_hosts: Vec<String>,
+}
+
+async fn process_request(
+    raw_request: String,
+    options: &ServerOptions,
+    client_cert_fingerprint: Option<String>,
+    handler: &dyn Handler,
+) -> Response {
+    match parse_request_line(raw_request) {
+        Ok(url) => {
+            if url.scheme() != "gemini"
+                || url.cannot_be_a_base()
+                || url.port().is_some_and(|p| p != options.port)
+            {
+                return Response {
+                    header: ResponseHeader::new(
+                        Status::ProxyRequestRefused,
+                        "Not a gemini request.",
+                    ),
+                    body: None,
+                };
+            }
+
+            if url
+                .host_str()
+                .is_some_and(|h| !options.allowed_hosts.contains(&h.to_string()))
+            {
+                return Response {
+                    header: ResponseHeader::new(
+                        Status::ProxyRequestRefused,
+                        "This host is not served here.",
+                    ),
+                    body: None,
+                };
+            }
+
+            handler
+                .handle(&Request {
+                    url,
+                    client_cert_fingerprint,
+                })
+                .await
+        }
+        Err(err) => Response {
+            header: ResponseHeader::new(Status::BadRequest, err),
+            body: None,
+        },
+    }
+}
+
+/// Hex-encoded SHA-256 fingerprint of a certificate's DER encoding, so it can
+/// be compared against an allow-list of pinned fingerprints.
+pub fn fingerprint(cert: &CertificateDer) -> String {
+    let digest = Sha256::digest(cert.as_ref());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Accepts any client certificate without validating it against a CA, so a
+/// [`Handler`] can inspect the presented fingerprint itself
+/// (trust-on-first-use) instead of relying on a certificate authority.
+#[derive(Debug)]
+pub struct PermissiveClientCertVerifier;
+
+impl ClientCertVerifier for PermissiveClientCertVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        default_verify_schemes()
+    }
+}
+
+/// Accepts any server certificate, mirroring how Gemini clients do
+/// trust-on-first-use instead of relying on a CA.
+#[derive(Debug)]
+struct PermissiveServerCertVerifier;
+
+impl ServerCertVerifier for PermissiveServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        default_verify_schemes()
+    }
+}
+
+fn default_verify_schemes() -> Vec<SignatureScheme> {
+    vec![
+        SignatureScheme::RSA_PKCS1_SHA256,
+        SignatureScheme::RSA_PKCS1_SHA384,
+        SignatureScheme::RSA_PKCS1_SHA512,
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        SignatureScheme::ED25519,
+    ]
+}
+
+/// Generates a self-signed certificate for `host` and writes the PEM-encoded
+/// certificate and private key to `cert_path`/`key_path`, for operators who
+/// haven't brought their own (trust-on-first-use instead of a CA).
+pub fn generate_self_signed_cert(
+    host: &str,
+    cert_path: &PathBuf,
+    key_path: &PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let certified_key = rcgen::generate_simple_self_signed(vec![host.to_string()])?;
+    fs::write(cert_path, certified_key.cert.pem())?;
+    fs::write(key_path, certified_key.key_pair.serialize_pem())?;
+    Ok(())
+}
+
+pub fn load_certs(path: &PathBuf) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = std::io::BufReader::new(fs::File::open(path)?);
+    rustls_pemfile::certs(&mut reader).collect()
+}
+
+pub fn load_private_key(path: &PathBuf) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = std::io::BufReader::new(fs::File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no private key found"))
+}
+
+async fn process_tls_stream(
+    stream: &mut TlsStream<TcpStream>,
+    options: &ServerOptions,
+    handler: &dyn Handler,
+) {
+    let client_cert_fingerprint = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .map(fingerprint);
+
+    let mut buffer = [0; 1026]; // 1024 for the url + CRLF
+    let n = stream
+        .read(&mut buffer)
+        .await
+        .expect("Error reading first line of stream.");
+
+    if n == 0 {
+        return;
+    }
+
+    if let Ok(raw_line) = String::from_utf8(buffer[0..n].into()) {
+        let request_line = match raw_line.split_once("\r\n") {
+            Some((l, _)) => l,
+            None => {
+                return;
+            }
+        };
+
+        let response = process_request(
+            request_line.to_string(),
+            options,
+            client_cert_fingerprint,
+            handler,
+        )
+        .await;
+
+        match response.header.status {
+            Status::Success => {
+                println!("Request: [{}] {}", Status::Success.code(), &request_line);
+            }
+            Status::BadRequest => println!("BadRequest: {request_line}"),
+            Status::NotFound => println!("Not found: {request_line}"),
+            _ => println!("Not able to process request: {request_line}"),
+        }
+
+        stream.write_all(&response.render_header()).await.unwrap();
+
+        match response.body {
+            Some(ResponseBody::Bytes(bytes)) => stream.write_all(&bytes).await.unwrap(),
+            Some(ResponseBody::File(mut file)) => {
+                io::copy(&mut file, stream).await.unwrap();
+            }
+            None => {}
+        }
+    }
+}
+
+/// Accepts connections on `tcp`, handling each over TLS and dispatching
+/// requests to `handler`. Runs forever.
+pub async fn serve(
+    tcp: TcpListener,
+    tls_acceptor: tokio_rustls::TlsAcceptor,
+    options: ServerOptions,
+    handler: Arc<dyn Handler>,
+) -> ! {
+    loop {
+        let (socket, remote_addr) = tcp.accept().await.expect("error accepting tcp connection");
+        let tls_acceptor = tls_acceptor.clone();
+        let options = options.clone();
+        let handler = Arc::clone(&handler);
+        println!("accept connection from {remote_addr}");
+        tokio::spawn(async move {
+            match tls_acceptor.accept(socket).await {
+                Ok(mut stream) => {
+                    process_tls_stream(&mut stream, &options, handler.as_ref()).await;
+                    stream.shutdown().await.expect("failed to shut down stream");
+                }
+
+                Err(e) => eprintln!("Connection from {remote_addr} closed: {e}"),
+            }
+        });
+    }
+}
+
+/// Errors returned by [`Client::request`].
+#[derive(Debug)]
+pub enum ClientError {
+    UnsupportedScheme,
+    UserinfoNotAllowed,
+    InvalidUrl,
+    Io(io::Error),
+    InvalidResponse,
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::UnsupportedScheme => write!(f, "only gemini:// URLs are supported"),
+            ClientError::UserinfoNotAllowed => {
+                write!(f, "URLs containing userinfo are not allowed")
+            }
+            ClientError::InvalidUrl => write!(f, "invalid gemini URL"),
+            ClientError::Io(e) => write!(f, "I/O error: {e}"),
+            ClientError::InvalidResponse => write!(f, "malformed response"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// A minimal Gemini client: connects over TLS, sends a `gemini://` URL, and
+/// parses the status line plus body into a [`Response`].
+pub struct Client {
+    connector: tokio_rustls::TlsConnector,
+}
+
+impl Client {
+    pub fn new() -> Client {
+        let tls_config = rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PermissiveServerCertVerifier))
+            .with_no_client_auth();
+        Client {
+            connector: tokio_rustls::TlsConnector::from(Arc::new(tls_config)),
+        }
+    }
+
+    /// Sends a `gemini://` request and returns the parsed response. Rejects
+    /// non-gemini schemes and URLs containing userinfo, per the spec.
+    pub async fn request(&self, url: &str) -> Result<Response, ClientError> {
+        let parsed = Url::parse(url).map_err(|_| ClientError::InvalidUrl)?;
+
+        if parsed.scheme() != "gemini" {
+            return Err(ClientError::UnsupportedScheme);
+        }
+
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            return Err(ClientError::UserinfoNotAllowed);
+        }
+
+        let host = parsed.host_str().ok_or(ClientError::InvalidUrl)?;
+        let port = parsed.port().unwrap_or(1965);
+
+        let tcp = TcpStream::connect((host, port))
+            .await
+            .map_err(ClientError::Io)?;
+        let server_name =
+            ServerName::try_from(host.to_string()).map_err(|_| ClientError::InvalidUrl)?;
+        let mut stream = self
+            .connector
+            .connect(server_name, tcp)
+            .await
+            .map_err(ClientError::Io)?;
+
+        stream
+            .write_all(format!("{url}\r\n").as_bytes())
+            .await
+            .map_err(ClientError::Io)?;
+
+        let mut raw = Vec::new();
+        stream
+            .read_to_end(&mut raw)
+            .await
+            .map_err(ClientError::Io)?;
+
+        parse_response(&raw)
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, ClientError> {
+    let header_end = raw
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .ok_or(ClientError::InvalidResponse)?;
+
+    let header_line =
+        std::str::from_utf8(&raw[..header_end]).map_err(|_| ClientError::InvalidResponse)?;
+    let (code, meta) = header_line.split_once(' ').unwrap_or((header_line, ""));
+    let code: u8 = code.parse().map_err(|_| ClientError::InvalidResponse)?;
+    let status = Status::from_code(code).ok_or(ClientError::InvalidResponse)?;
+
+    let body = raw[header_end + 2..].to_vec();
+    let body = if matches!(status, Status::Success) && !body.is_empty() {
+        Some(ResponseBody::Bytes(body))
+    } else {
+        None
+    };
+
+    Ok(Response {
+        header: ResponseHeader::new(status, meta),
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn content_type_is_detected_by_extension() {
+        assert_eq!(content_type(Path::new("index.gmi")), "text/gemini");
+        assert_eq!(content_type(Path::new("index.gemini")), "text/gemini");
+        assert_eq!(content_type(Path::new("notes.TXT")), "text/plain");
+        assert_eq!(content_type(Path::new("page.html")), "text/html");
+        assert_eq!(content_type(Path::new("page.htm")), "text/html");
+        assert_eq!(content_type(Path::new("logo.png")), "image/png");
+        assert_eq!(content_type(Path::new("photo.jpg")), "image/jpeg");
+        assert_eq!(content_type(Path::new("photo.jpeg")), "image/jpeg");
+        assert_eq!(content_type(Path::new("anim.gif")), "image/gif");
+        assert_eq!(content_type(Path::new("doc.pdf")), "application/pdf");
+        assert_eq!(
+            content_type(Path::new("data.bin")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type(Path::new("no_extension")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn directory_listing_sorts_dirs_first_then_percent_encodes_names() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("shooting-star-listing-test-{nanos}"));
+        fs::create_dir_all(dir.join("b-dir")).unwrap();
+        fs::create_dir_all(dir.join("a-dir")).unwrap();
+        fs::write(dir.join("a-file.gmi"), "").unwrap();
+        fs::write(dir.join("weird name?.gmi"), "").unwrap();
+
+        let gemtext = String::from_utf8(render_directory_listing(&dir)).unwrap();
+        let lines: Vec<&str> = gemtext.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "=> a-dir/ a-dir/",
+                "=> b-dir/ b-dir/",
+                "=> a-file.gmi a-file.gmi",
+                "=> weird%20name%3F.gmi weird name?.gmi",
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Creates `<tmp>/root/secret/file.gmi` (and an `index.gmi`), plus an
+    /// `<tmp>/outside.gmi` sibling of `root` to probe traversal, returning
+    /// the `root` path. Caller is responsible for removing `root`'s parent.
+    fn test_root() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let base = std::env::temp_dir().join(format!("shooting-star-test-{nanos}"));
+        let root = base.join("root");
+        fs::create_dir_all(root.join("secret")).unwrap();
+        fs::write(root.join("secret/file.gmi"), "top secret\n").unwrap();
+        fs::write(root.join("index.gmi"), "welcome\n").unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/index.gmi"), "docs\n").unwrap();
+        fs::write(base.join("outside.gmi"), "should not be reachable\n").unwrap();
+        root
+    }
+
+    fn test_handler(root: PathBuf) -> FileHandler {
+        FileHandler {
+            root,
+            protected_paths: vec!["/secret".to_string()],
+            allowed_fingerprints: vec!["deadbeef".to_string()],
+            auto_index: false,
+        }
+    }
+
+    fn test_request(path: &str, client_cert_fingerprint: Option<&str>) -> Request {
+        Request {
+            url: Url::parse(&format!("gemini://example.com{path}")).unwrap(),
+            client_cert_fingerprint: client_cert_fingerprint.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn directory_without_trailing_slash_redirects() {
+        let root = test_root();
+        let response = test_handler(root.clone())
+            .handle(&test_request("/docs", None))
+            .await;
+        assert_eq!(
+            response.header.status.code(),
+            Status::RedirectTemporary.code()
+        );
+        assert_eq!(response.header.meta, "/docs/");
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn protected_path_without_cert_requires_certificate() {
+        let root = test_root();
+        let response = test_handler(root.clone())
+            .handle(&test_request("/secret/file.gmi", None))
+            .await;
+        assert_eq!(response.header.status.code(), Status::CertificateRequired.code());
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn protected_path_with_unauthorized_cert_is_rejected() {
+        let root = test_root();
+        let response = test_handler(root.clone())
+            .handle(&test_request("/secret/file.gmi", Some("not-allowed")))
+            .await;
+        assert_eq!(
+            response.header.status.code(),
+            Status::CertificateNotAuthorized.code()
+        );
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn protected_path_with_authorized_cert_is_served() {
+        let root = test_root();
+        let response = test_handler(root.clone())
+            .handle(&test_request("/secret/file.gmi", Some("deadbeef")))
+            .await;
+        assert_eq!(response.header.status.code(), Status::Success.code());
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn doubled_leading_slash_still_hits_the_protected_path_gate() {
+        let root = test_root();
+        let response = test_handler(root.clone())
+            .handle(&test_request("//secret/file.gmi", None))
+            .await;
+        assert_eq!(response.header.status.code(), Status::CertificateRequired.code());
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn symlink_escaping_root_is_rejected() {
+        let root = test_root();
+        std::os::unix::fs::symlink(
+            root.parent().unwrap().join("outside.gmi"),
+            root.join("escape"),
+        )
+        .unwrap();
+        let response = test_handler(root.clone())
+            .handle(&test_request("/escape", None))
+            .await;
+        assert_eq!(response.header.status.code(), Status::BadRequest.code());
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+}